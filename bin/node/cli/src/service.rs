@@ -17,410 +17,650 @@
 ///////////////////////////////////////////////////////////////////////////////
 //! Service and ServiceFactory implementation. Specialized wrapper over Substrate service.
 
-/// Native executor for Robonomics runtimes (benchmark enabled).
-#[cfg(feature = "frame-benchmarking")]
-pub mod executor {
-    sc_executor::native_executor_instance!(
-        pub Robonomics,
-        robonomics_runtime::api::dispatch,
-        robonomics_runtime::native_version,
-        frame_benchmarking::benchmarking::HostFunctions,
-    );
+use std::sync::Arc;
+
+use futures::prelude::*;
+use sc_client_api::ExecutorProvider;
+use sc_network::Event;
+use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
+
+/// Default GRANDPA gossip duration, used when a chain spec doesn't set `grandpaGossipDurationMillis`.
+const GRANDPA_GOSSIP_DURATION_MILLIS: u64 = 333;
+/// Default number of blocks between GRANDPA justifications, used when a chain spec doesn't
+/// set `grandpaJustificationPeriod`.
+const GRANDPA_JUSTIFICATION_PERIOD: u32 = 512;
+
+/// Default fraction of a BABE slot a proposer may spend building a block, used when a
+/// chain spec doesn't set `babeProposalSlotPortion`.
+const BABE_PROPOSAL_SLOT_PORTION: f32 = 1.0 / 3.0;
+/// Hard ceiling on the fraction of a BABE slot a proposer may spend building a block,
+/// regardless of how much headroom the dynamic [`BABE_PROPOSAL_SLOT_PORTION`] leaves, used
+/// when a chain spec doesn't set `babeMaxProposalSlotPortion`.
+const BABE_MAX_PROPOSAL_SLOT_PORTION: f32 = 0.5;
+
+/// Reads the GRANDPA gossip duration from chain spec properties, falling back to
+/// [`GRANDPA_GOSSIP_DURATION_MILLIS`].
+fn grandpa_gossip_duration(properties: &sc_chain_spec::Properties) -> std::time::Duration {
+    let millis = properties
+        .get("grandpaGossipDurationMillis")
+        .and_then(|value| value.as_u64())
+        .unwrap_or(GRANDPA_GOSSIP_DURATION_MILLIS);
+    std::time::Duration::from_millis(millis)
+}
 
-    sc_executor::native_executor_instance!(
-        pub Ipci,
-        ipci_runtime::api::dispatch,
-        ipci_runtime::native_version,
-        frame_benchmarking::benchmarking::HostFunctions,
-    );
+/// Reads the GRANDPA justification period from chain spec properties, falling back to
+/// [`GRANDPA_JUSTIFICATION_PERIOD`].
+fn grandpa_justification_period(properties: &sc_chain_spec::Properties) -> u32 {
+    properties
+        .get("grandpaJustificationPeriod")
+        .and_then(|value| value.as_u64())
+        .map(|period| period as u32)
+        .unwrap_or(GRANDPA_JUSTIFICATION_PERIOD)
+}
+
+#[cfg(test)]
+mod grandpa_config_tests {
+    use super::*;
+
+    #[test]
+    fn grandpa_gossip_duration_defaults_when_unset() {
+        let properties = sc_chain_spec::Properties::new();
+        assert_eq!(
+            grandpa_gossip_duration(&properties),
+            std::time::Duration::from_millis(GRANDPA_GOSSIP_DURATION_MILLIS),
+        );
+    }
+
+    #[test]
+    fn grandpa_gossip_duration_reads_chain_spec_override() {
+        let mut properties = sc_chain_spec::Properties::new();
+        properties.insert("grandpaGossipDurationMillis".into(), 1_000.into());
+        assert_eq!(grandpa_gossip_duration(&properties), std::time::Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn grandpa_justification_period_defaults_when_unset() {
+        let properties = sc_chain_spec::Properties::new();
+        assert_eq!(grandpa_justification_period(&properties), GRANDPA_JUSTIFICATION_PERIOD);
+    }
+
+    #[test]
+    fn grandpa_justification_period_reads_chain_spec_override() {
+        let mut properties = sc_chain_spec::Properties::new();
+        properties.insert("grandpaJustificationPeriod".into(), 100.into());
+        assert_eq!(grandpa_justification_period(&properties), 100);
+    }
+}
+
+/// Reads the fraction of a BABE slot a proposer may spend building a block from chain spec
+/// properties, falling back to [`BABE_PROPOSAL_SLOT_PORTION`].
+fn babe_proposal_slot_portion(properties: &sc_chain_spec::Properties) -> f32 {
+    properties
+        .get("babeProposalSlotPortion")
+        .and_then(|value| value.as_f64())
+        .map(|portion| portion as f32)
+        .unwrap_or(BABE_PROPOSAL_SLOT_PORTION)
+}
+
+/// Reads the hard ceiling on the fraction of a BABE slot a proposer may spend building a
+/// block from chain spec properties, falling back to [`BABE_MAX_PROPOSAL_SLOT_PORTION`].
+fn babe_max_proposal_slot_portion(properties: &sc_chain_spec::Properties) -> f32 {
+    properties
+        .get("babeMaxProposalSlotPortion")
+        .and_then(|value| value.as_f64())
+        .map(|portion| portion as f32)
+        .unwrap_or(BABE_MAX_PROPOSAL_SLOT_PORTION)
 }
 
-/// Native executor for Robonomics runtimes (benchmark disabled).
-#[cfg(not(feature = "frame-benchmarking"))]
+#[cfg(test)]
+mod babe_config_tests {
+    use super::*;
+
+    #[test]
+    fn babe_proposal_slot_portion_defaults_when_unset() {
+        let properties = sc_chain_spec::Properties::new();
+        assert_eq!(babe_proposal_slot_portion(&properties), BABE_PROPOSAL_SLOT_PORTION);
+    }
+
+    #[test]
+    fn babe_proposal_slot_portion_reads_chain_spec_override() {
+        let mut properties = sc_chain_spec::Properties::new();
+        properties.insert("babeProposalSlotPortion".into(), 0.25.into());
+        assert_eq!(babe_proposal_slot_portion(&properties), 0.25);
+    }
+
+    #[test]
+    fn babe_max_proposal_slot_portion_defaults_when_unset() {
+        let properties = sc_chain_spec::Properties::new();
+        assert_eq!(babe_max_proposal_slot_portion(&properties), BABE_MAX_PROPOSAL_SLOT_PORTION);
+    }
+
+    #[test]
+    fn babe_max_proposal_slot_portion_reads_chain_spec_override() {
+        let mut properties = sc_chain_spec::Properties::new();
+        properties.insert("babeMaxProposalSlotPortion".into(), 0.75.into());
+        assert_eq!(babe_max_proposal_slot_portion(&properties), 0.75);
+    }
+}
+
+/// Wasm-first executors for Robonomics runtimes, falling back to the
+/// native runtime only when its version matches the on-chain wasm blob.
 pub mod executor {
-    sc_executor::native_executor_instance!(
-        pub Robonomics,
-        robonomics_runtime::api::dispatch,
-        robonomics_runtime::native_version,
-    );
+    /// Robonomics runtime executor dispatch, wired into a [`sc_executor::NativeElseWasmExecutor`].
+    pub struct Robonomics;
 
-    sc_executor::native_executor_instance!(
-        pub Ipci,
-        ipci_runtime::api::dispatch,
-        ipci_runtime::native_version,
-    );
+    impl sc_executor::NativeExecutionDispatch for Robonomics {
+        #[cfg(feature = "frame-benchmarking")]
+        type ExtendHostFunctions = frame_benchmarking::benchmarking::HostFunctions;
+        #[cfg(not(feature = "frame-benchmarking"))]
+        type ExtendHostFunctions = ();
+
+        fn dispatch(method: &str, data: &[u8]) -> Option<Vec<u8>> {
+            robonomics_runtime::api::dispatch(method, data)
+        }
+
+        fn native_version() -> sc_executor::NativeVersion {
+            robonomics_runtime::native_version()
+        }
+    }
+
+    /// IPCI runtime executor dispatch, wired into a [`sc_executor::NativeElseWasmExecutor`].
+    pub struct Ipci;
+
+    impl sc_executor::NativeExecutionDispatch for Ipci {
+        #[cfg(feature = "frame-benchmarking")]
+        type ExtendHostFunctions = frame_benchmarking::benchmarking::HostFunctions;
+        #[cfg(not(feature = "frame-benchmarking"))]
+        type ExtendHostFunctions = ();
+
+        fn dispatch(method: &str, data: &[u8]) -> Option<Vec<u8>> {
+            ipci_runtime::api::dispatch(method, data)
+        }
+
+        fn native_version() -> sc_executor::NativeVersion {
+            ipci_runtime::native_version()
+        }
+    }
+
+    /// Build a wasm-first, native-fallback executor sized from the node `Configuration`.
+    ///
+    /// The wasm runtime is preferred so a chain can upgrade forkless even when the
+    /// node binary lags behind; the heap allocation strategy is read from the config
+    /// so low-RAM robot deployments can trade off memory for a static, pre-sized heap.
+    pub fn build_executor<Dispatch: sc_executor::NativeExecutionDispatch>(
+        config: &sc_service::Configuration,
+    ) -> sc_executor::NativeElseWasmExecutor<Dispatch> {
+        let heap_alloc_strategy = config
+            .default_heap_pages
+            .map_or(sc_executor::HeapAllocStrategy::Dynamic { maximum_pages: None }, |pages| {
+                sc_executor::HeapAllocStrategy::Static { extra_pages: pages as _ }
+            });
+
+        sc_executor::NativeElseWasmExecutor::<Dispatch>::new(
+            config.wasm_method,
+            Some(heap_alloc_strategy),
+            config.max_runtime_instances,
+            config.runtime_cache_size,
+        )
+    }
 }
 
-/// Starts a `ServiceBuilder` for a full service.
+/// Full client type, generic over the runtime API and native executor dispatch.
+pub type FullClient<RuntimeApi, Executor> =
+    sc_service::TFullClient<node_primitives::Block, RuntimeApi, sc_executor::NativeElseWasmExecutor<Executor>>;
+type FullBackend = sc_service::TFullBackend<node_primitives::Block>;
+type FullSelectChain = sc_consensus::LongestChain<FullBackend, node_primitives::Block>;
+type FullGrandpaBlockImport<RuntimeApi, Executor> = sc_finality_grandpa::GrandpaBlockImport<
+    FullBackend,
+    node_primitives::Block,
+    FullClient<RuntimeApi, Executor>,
+    FullSelectChain,
+>;
+
+/// Creates the client, backend, keystore, task manager, import queue and transaction pool
+/// a service needs, without standing up the network or consensus tasks.
 ///
-/// Use this macro if you don't actually need the full service, but just the builder in order to
-/// be able to perform chain operations.
-#[macro_export]
-macro_rules! new_full_start {
-    ($config:expr, $runtime:ty, $executor:ty) => {{
-        let mut import_setup = None;
-        let inherent_data_providers = sp_inherents::InherentDataProviders::new();
-
-        let builder = sc_service::ServiceBuilder::new_full::<
-            node_primitives::Block,
-            $runtime,
-            $executor,
-        >($config)?
-        .with_select_chain(|_config, backend| Ok(sc_consensus::LongestChain::new(backend.clone())))?
-        .with_transaction_pool(|config, client, _fetcher, prometheus_registry| {
-            let pool_api = sc_transaction_pool::FullChainApi::new(client.clone());
-            Ok(sc_transaction_pool::BasicPool::new(
-                config,
-                std::sync::Arc::new(pool_api),
-                prometheus_registry,
-            ))
-        })?
-        .with_import_queue(
-            |_config,
-             client,
-             mut select_chain,
-             _transaction_pool,
-             spawn_task_handle,
-             prometheus_registry| {
-                let select_chain = select_chain
-                    .take()
-                    .ok_or_else(|| sc_service::Error::SelectChainRequired)?;
-                let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
-                    client.clone(),
-                    &(client.clone() as std::sync::Arc<_>),
-                    select_chain,
-                )?;
-                let justification_import = grandpa_block_import.clone();
-
-                let (babe_block_import, babe_link) = sc_consensus_babe::block_import(
-                    sc_consensus_babe::Config::get_or_compute(&*client)?,
-                    grandpa_block_import,
-                    client.clone(),
-                )?;
-
-                let import_queue = sc_consensus_babe::import_queue(
-                    babe_link.clone(),
-                    babe_block_import.clone(),
-                    Some(Box::new(justification_import)),
-                    None,
-                    client,
-                    inherent_data_providers.clone(),
-                    spawn_task_handle,
-                    prometheus_registry,
-                )?;
-
-                import_setup = Some((babe_block_import, grandpa_link, babe_link));
-                Ok(import_queue)
-            },
-        )?;
-
-        (builder, import_setup, inherent_data_providers)
-    }};
+/// Used by `new_full` as well as CLI subcommands (chain revert, state export, db checks,
+/// benchmarking) that only need to poke the chain state. `new_light` does not use it: it
+/// builds its own light client, backend, transaction pool and import queue.
+pub fn new_partial<RuntimeApi, Executor>(
+    config: &Configuration,
+) -> Result<
+    sc_service::PartialComponents<
+        FullClient<RuntimeApi, Executor>,
+        FullBackend,
+        FullSelectChain,
+        sc_consensus::DefaultImportQueue<node_primitives::Block, FullClient<RuntimeApi, Executor>>,
+        sc_transaction_pool::FullPool<node_primitives::Block, FullClient<RuntimeApi, Executor>>,
+        (
+            sc_consensus_babe::BabeBlockImport<
+                node_primitives::Block,
+                FullClient<RuntimeApi, Executor>,
+                FullGrandpaBlockImport<RuntimeApi, Executor>,
+            >,
+            sc_finality_grandpa::LinkHalf<node_primitives::Block, FullClient<RuntimeApi, Executor>, FullSelectChain>,
+            sc_consensus_babe::BabeLink<node_primitives::Block>,
+            sp_inherents::InherentDataProviders,
+        ),
+    >,
+    ServiceError,
+>
+where
+    Executor: sc_executor::NativeExecutionDispatch + 'static,
+    RuntimeApi: sp_api::ConstructRuntimeApi<node_primitives::Block, FullClient<RuntimeApi, Executor>>
+        + Send
+        + Sync
+        + 'static,
+    RuntimeApi::RuntimeApi: node_primitives::RuntimeApiCollection<StateBackend = sc_client_api::StateBackendFor<FullBackend, node_primitives::Block>>,
+{
+    let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+    let executor = executor::build_executor::<Executor>(config);
+
+    let (client, backend, keystore_container, task_manager) =
+        sc_service::new_full_parts::<node_primitives::Block, RuntimeApi, _>(config, None, executor)?;
+    let client = Arc::new(client);
+
+    let select_chain = sc_consensus::LongestChain::new(backend.clone());
+
+    let transaction_pool = sc_transaction_pool::BasicPool::new_full(
+        config.transaction_pool.clone(),
+        config.role.is_authority().into(),
+        config.prometheus_registry(),
+        task_manager.spawn_handle(),
+        client.clone(),
+    );
+
+    let (grandpa_block_import, grandpa_link) = sc_finality_grandpa::block_import(
+        client.clone(),
+        &(client.clone() as Arc<_>),
+        select_chain.clone(),
+    )?;
+    let justification_import = grandpa_block_import.clone();
+
+    let (babe_block_import, babe_link) = sc_consensus_babe::block_import(
+        sc_consensus_babe::Config::get_or_compute(&*client)?,
+        grandpa_block_import,
+        client.clone(),
+    )?;
+
+    let import_queue = sc_consensus_babe::import_queue(
+        babe_link.clone(),
+        babe_block_import.clone(),
+        Some(Box::new(justification_import)),
+        None,
+        client.clone(),
+        inherent_data_providers.clone(),
+        &task_manager.spawn_handle(),
+        config.prometheus_registry(),
+    )?;
+
+    Ok(sc_service::PartialComponents {
+        client,
+        backend,
+        task_manager,
+        keystore_container,
+        select_chain,
+        import_queue,
+        transaction_pool: Arc::new(transaction_pool),
+        other: (babe_block_import, grandpa_link, babe_link, inherent_data_providers),
+    })
 }
 
 /// Creates a full service from the configuration.
-#[macro_export]
-macro_rules! new_full {
-    ($config:expr, $runtime:ty, $executor:ty) => {{
-        use futures::prelude::*;
-        use sc_network::Event;
-        use sc_client_api::ExecutorProvider;
-        use std::sync::Arc;
-
-        let (
-            role,
-            force_authoring,
-            name,
-            disable_grandpa,
-        ) = (
-            $config.role.clone(),
-            $config.force_authoring,
-            $config.network.node_name.clone(),
-            $config.disable_grandpa,
+pub fn new_full<RuntimeApi, Executor>(config: Configuration) -> Result<TaskManager, ServiceError>
+where
+    Executor: sc_executor::NativeExecutionDispatch + 'static,
+    RuntimeApi: sp_api::ConstructRuntimeApi<node_primitives::Block, FullClient<RuntimeApi, Executor>>
+        + Send
+        + Sync
+        + 'static,
+    RuntimeApi::RuntimeApi: node_primitives::RuntimeApiCollection<StateBackend = sc_client_api::StateBackendFor<FullBackend, node_primitives::Block>>,
+{
+    let role = config.role.clone();
+    let force_authoring = config.force_authoring;
+    let name = config.network.node_name.clone();
+    let disable_grandpa = config.disable_grandpa;
+    let grandpa_gossip_duration = grandpa_gossip_duration(&config.chain_spec.properties());
+    let grandpa_justification_period = grandpa_justification_period(&config.chain_spec.properties());
+    let babe_proposal_slot_portion = babe_proposal_slot_portion(&config.chain_spec.properties());
+    let babe_max_proposal_slot_portion = babe_max_proposal_slot_portion(&config.chain_spec.properties());
+
+    #[cfg(feature = "ros")]
+    let system_info = substrate_ros_api::system::SystemInfo {
+        impl_name: config.impl_name.into(),
+        impl_version: config.impl_version.into(),
+        chain_name: config.chain_spec.name().into(),
+        chain_type: config.chain_spec.chain_type().clone(),
+        properties: config.chain_spec.properties().clone(),
+    };
+
+    let sc_service::PartialComponents {
+        client,
+        backend,
+        mut task_manager,
+        import_queue,
+        keystore_container,
+        select_chain,
+        transaction_pool,
+        other: (block_import, grandpa_link, babe_link, inherent_data_providers),
+    } = new_partial::<RuntimeApi, Executor>(&config)?;
+
+    // The warp sync provider walks GRANDPA authority-set change justifications from
+    // genesis forward to the latest finalized set, so a joining node can verify the
+    // authority handoff chain without replaying extrinsics.
+    let shared_authority_set = grandpa_link.shared_authority_set().clone();
+    let warp_sync = Arc::new(sc_finality_grandpa::warp_proof::NetworkProvider::new(
+        backend.clone(),
+        shared_authority_set,
+        Vec::new(),
+    ));
+
+    let (network, system_rpc_tx, network_starter) = sc_service::build_network(sc_service::BuildNetworkParams {
+        config: &config,
+        client: client.clone(),
+        transaction_pool: transaction_pool.clone(),
+        spawn_handle: task_manager.spawn_handle(),
+        import_queue,
+        block_announce_validator_builder: None,
+        warp_sync: Some(warp_sync),
+    })?;
+
+    // Binds the node's transaction pool to the offchain-worker subsystem so runtime
+    // offchain code (and the ROS bridge) can author and submit signed extrinsics
+    // directly, instead of routing everything through external RPC.
+    let offchain_tx_pool_factory = sc_transaction_pool_api::OffchainTransactionPoolFactory::new(transaction_pool.clone());
+    client
+        .execution_extensions()
+        .set_offchain_transaction_pool(offchain_tx_pool_factory.clone());
+
+    if config.offchain_worker.enabled {
+        sc_service::build_offchain_workers(
+            &config,
+            task_manager.spawn_handle(),
+            client.clone(),
+            network.clone(),
         );
-        #[cfg(feature = "ros")]
-        let system_info = substrate_ros_api::system::SystemInfo {
-            impl_name: $config.impl_name.into(),
-            impl_version: $config.impl_version.into(),
-            chain_name: $config.chain_spec.name().into(),
-            chain_type: $config.chain_spec.chain_type().clone(),
-            properties: $config.chain_spec.properties().clone(),
-        };
+    }
 
-        let (builder, mut import_setup, inherent_data_providers) =
-            new_full_start!($config, $runtime, $executor);
-
-        let service = builder
-            .with_finality_proof_provider(|client, backend| {
-                // GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
-                let provider = client as Arc<dyn sc_finality_grandpa::StorageAndProofProvider<_, _>>;
-                Ok(Arc::new(sc_finality_grandpa::FinalityProofProvider::new(backend, provider)) as _)
-            })?
-            .build()?;
-
-        let (block_import, grandpa_link, babe_link) = import_setup.take()
-                .expect("Link Half and Block Import are present for Full Services or setup failed before. qed");
-
-        if let sc_service::config::Role::Authority { .. } = &role {
-            let proposer = sc_basic_authorship::ProposerFactory::new(
-                service.client(),
-                service.transaction_pool(),
-                service.prometheus_registry().as_ref(),
-            );
-
-            let client = service.client();
-            let select_chain = service.select_chain()
-                .ok_or(sc_service::Error::SelectChainRequired)?;
-
-            let can_author_with =
-                sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
-
-            let babe_config = sc_consensus_babe::BabeParams {
-                keystore: service.keystore(),
-                client,
-                select_chain,
-                env: proposer,
-                block_import,
-                sync_oracle: service.network(),
-                inherent_data_providers: inherent_data_providers.clone(),
-                force_authoring,
-                babe_link,
-                can_author_with,
-            };
-
-            let babe = sc_consensus_babe::start_babe(babe_config)?;
-            service.spawn_essential_task("babe-proposer", babe);
-        }
+    let prometheus_registry = config.prometheus_registry().cloned();
+
+    // Sinks that fan GRANDPA's voter-connect notifications out to telemetry, mirroring
+    // what `spawn_tasks` wires up for the rest of the service's telemetry reporting.
+    let telemetry_connection_sinks = sc_service::TelemetryConnectionSinks::default();
+
+    sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+        network: network.clone(),
+        client: client.clone(),
+        keystore: keystore_container.sync_keystore(),
+        task_manager: &mut task_manager,
+        transaction_pool: transaction_pool.clone(),
+        rpc_extensions_builder: Box::new(|_, _| Ok(())),
+        backend: backend.clone(),
+        system_rpc_tx,
+        telemetry_connection_sinks: telemetry_connection_sinks.clone(),
+        config,
+    })?;
+
+    if let sc_service::config::Role::Authority { .. } = &role {
+        let proposer = sc_basic_authorship::ProposerFactory::new(
+            client.clone(),
+            transaction_pool.clone(),
+            prometheus_registry.as_ref(),
+        );
 
-        // Spawn authority discovery module.
-        if matches!(role, sc_service::config::Role::Authority{..} | sc_service::config::Role::Sentry {..}) {
-            let (sentries, authority_discovery_role) = match role {
-                sc_service::config::Role::Authority { ref sentry_nodes } => (
-                    sentry_nodes.clone(),
-                    sc_authority_discovery::Role::Authority (
-                        service.keystore(),
-                    ),
-                ),
-                sc_service::config::Role::Sentry {..} => (
-                    vec![],
-                    sc_authority_discovery::Role::Sentry,
-                ),
-                _ => unreachable!("Due to outer matches! constraint; qed.")
-            };
-
-            let network = service.network();
-            let dht_event_stream = network.event_stream("authority-discovery").filter_map(|e| async move { match e {
-                Event::Dht(e) => Some(e),
-                _ => None,
-            }}).boxed();
-            let authority_discovery = sc_authority_discovery::AuthorityDiscovery::new(
-                service.client(),
-                network,
-                sentries,
-                dht_event_stream,
-                authority_discovery_role,
-                service.prometheus_registry(),
-            );
-
-            service.spawn_task("authority-discovery", authority_discovery);
-        }
+        let can_author_with = sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
 
-        // if the node isn't actively participating in consensus then it doesn't
-        // need a keystore, regardless of which protocol we use below.
-        let keystore = if role.is_authority() {
-            Some(service.keystore())
-        } else {
-            None
+        let babe_config = sc_consensus_babe::BabeParams {
+            keystore: keystore_container.sync_keystore(),
+            client: client.clone(),
+            select_chain,
+            env: proposer,
+            block_import,
+            sync_oracle: network.clone(),
+            inherent_data_providers: inherent_data_providers.clone(),
+            force_authoring,
+            babe_link,
+            can_author_with,
+            // Bound how much of a slot a proposer may spend building a block, with a hard
+            // ceiling underneath so a resource-constrained authority never blows its slot
+            // and misses the block.
+            block_proposal_slot_portion: sc_consensus_babe::SlotProportion::new(babe_proposal_slot_portion),
+            max_block_proposal_slot_portion: Some(sc_consensus_babe::SlotProportion::new(
+                babe_max_proposal_slot_portion,
+            )),
         };
 
-        let config = sc_finality_grandpa::Config {
-            // FIXME #1578 make this available through chainspec
-            gossip_duration: std::time::Duration::from_millis(333),
-            justification_period: 512,
-            name: Some(name),
-            observer_enabled: false,
-            keystore,
-            is_authority: role.is_network_authority(),
+        let babe = sc_consensus_babe::start_babe(babe_config)?;
+        task_manager
+            .spawn_essential_handle()
+            .spawn_blocking("babe-proposer", babe);
+    }
+
+    // Spawn authority discovery module.
+    if matches!(
+        role,
+        sc_service::config::Role::Authority { .. } | sc_service::config::Role::Sentry { .. }
+    ) {
+        let (sentries, authority_discovery_role) = match role {
+            sc_service::config::Role::Authority { ref sentry_nodes } => (
+                sentry_nodes.clone(),
+                sc_authority_discovery::Role::Authority(keystore_container.sync_keystore()),
+            ),
+            sc_service::config::Role::Sentry { .. } => (vec![], sc_authority_discovery::Role::Sentry),
+            _ => unreachable!("Due to outer matches! constraint; qed."),
         };
 
-        let enable_grandpa = !disable_grandpa;
-        if enable_grandpa {
-            // start the full GRANDPA voter
-            // NOTE: non-authorities could run the GRANDPA observer protocol, but at
-            // this point the full voter should provide better guarantees of block
-            // and vote data availability than the observer. The observer has not
-            // been tested extensively yet and having most nodes in a network run it
-            // could lead to finality stalls.
-            let grandpa_config = sc_finality_grandpa::GrandpaParams {
-                config,
-                link: grandpa_link,
-                network: service.network(),
-                inherent_data_providers: inherent_data_providers.clone(),
-                telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
-                voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
-                prometheus_registry: service.prometheus_registry(),
-                shared_voter_state: sc_finality_grandpa::SharedVoterState::empty(),
-            };
-
-            // the GRANDPA voter task is considered infallible, i.e.
-            // if it fails we take down the service with it.
-            service.spawn_essential_task(
-                "grandpa-voter",
-                sc_finality_grandpa::run_grandpa_voter(grandpa_config)?
-            );
-        } else {
-            sc_finality_grandpa::setup_disabled_grandpa(
-                service.client(),
-                &inherent_data_providers,
-                service.network(),
-            )?;
-        }
+        let dht_event_stream = network
+            .event_stream("authority-discovery")
+            .filter_map(|e| async move {
+                match e {
+                    Event::Dht(e) => Some(e),
+                    _ => None,
+                }
+            })
+            .boxed();
+        let authority_discovery = sc_authority_discovery::AuthorityDiscovery::new(
+            client.clone(),
+            network.clone(),
+            sentries,
+            dht_event_stream,
+            authority_discovery_role,
+            prometheus_registry.clone(),
+        );
+
+        task_manager
+            .spawn_handle()
+            .spawn("authority-discovery", authority_discovery);
+    }
+
+    // if the node isn't actively participating in consensus then it doesn't
+    // need a keystore, regardless of which protocol we use below.
+    let keystore = if role.is_authority() {
+        Some(keystore_container.sync_keystore())
+    } else {
+        None
+    };
+
+    let grandpa_config = sc_finality_grandpa::Config {
+        gossip_duration: grandpa_gossip_duration,
+        justification_period: grandpa_justification_period,
+        name: Some(name),
+        observer_enabled: false,
+        keystore,
+        is_authority: role.is_network_authority(),
+    };
+
+    let enable_grandpa = !disable_grandpa;
+    if enable_grandpa {
+        // start the full GRANDPA voter
+        // NOTE: non-authorities could run the GRANDPA observer protocol, but at
+        // this point the full voter should provide better guarantees of block
+        // and vote data availability than the observer. The observer has not
+        // been tested extensively yet and having most nodes in a network run it
+        // could lead to finality stalls.
+        let grandpa_params = sc_finality_grandpa::GrandpaParams {
+            config: grandpa_config,
+            link: grandpa_link,
+            network: network.clone(),
+            inherent_data_providers: inherent_data_providers.clone(),
+            telemetry_on_connect: Some(telemetry_connection_sinks.on_connect_stream()),
+            voting_rule: sc_finality_grandpa::VotingRulesBuilder::default().build(),
+            prometheus_registry: prometheus_registry.clone(),
+            shared_voter_state: sc_finality_grandpa::SharedVoterState::empty(),
+        };
 
-        #[cfg(feature = "ros")]
-        { if rosrust::try_init_with_options("robonomics", false).is_ok() {
-            let (substrate_ros_services, publish_task) =
-                substrate_ros_api::start(
-                    system_info,
-                    service.client(),
-                    service.network(),
-                    service.transaction_pool(),
-                    service.keystore(),
-                ).map_err(|e| format!("Substrate ROS: {}", e))?;
-
-            let on_exit = service.on_exit().then(move |_| {
+        // the GRANDPA voter task is considered infallible, i.e.
+        // if it fails we take down the service with it.
+        task_manager.spawn_essential_handle().spawn_blocking(
+            "grandpa-voter",
+            sc_finality_grandpa::run_grandpa_voter(grandpa_params)?,
+        );
+    } else {
+        sc_finality_grandpa::setup_disabled_grandpa(client.clone(), &inherent_data_providers, network.clone())?;
+    }
+
+    #[cfg(feature = "ros")]
+    {
+        if rosrust::try_init_with_options("robonomics", false).is_ok() {
+            let (substrate_ros_services, publish_task) = substrate_ros_api::start(
+                system_info,
+                client.clone(),
+                network.clone(),
+                transaction_pool.clone(),
+                keystore_container.sync_keystore(),
+                offchain_tx_pool_factory.clone(),
+            )
+            .map_err(|e| format!("Substrate ROS: {}", e))?;
+
+            let on_exit = task_manager.future().then(move |_| {
                 // Keep ROS services&subscribers alive until on_exit signal reached
                 let _ = substrate_ros_services;
                 futures::future::ready(())
             });
 
-            let ros_task = futures::future::join(
-                publish_task,
-                on_exit,
-            ).boxed().map(|_| ());
+            let ros_task = futures::future::join(publish_task, on_exit).boxed().map(|_| ());
 
-            service.spawn_task("substrate-ros", ros_task);
+            task_manager.spawn_handle().spawn("substrate-ros", ros_task);
         } else {
             log::warn!("ROS integration disabled because of initialization failure");
-        }}
+        }
+    }
 
-        Ok(service)
-    }};
+    network_starter.start_network();
+    Ok(task_manager)
 }
 
 /// Creates a light service from the configuration.
-#[macro_export]
-macro_rules! new_light {
-    ($config:expr, $runtime:ty, $executor:ty) => {{
-        use std::sync::Arc;
-
-        let inherent_data_providers = sp_inherents::InherentDataProviders::new();
-
-        sc_service::ServiceBuilder::new_light::<node_primitives::Block, $runtime, $executor>(
-            $config,
-        )?
-        .with_select_chain(|_, backend| Ok(sc_consensus::LongestChain::new(backend.clone())))?
-        .with_transaction_pool(|config, client, fetcher, prometheus_registry| {
-            let fetcher = fetcher
-                .ok_or_else(|| "Trying to start light transaction pool without active fetcher")?;
-            let pool_api = sc_transaction_pool::LightChainApi::new(client.clone(), fetcher.clone());
-            let pool = sc_transaction_pool::BasicPool::with_revalidation_type(
-                config,
-                Arc::new(pool_api),
-                prometheus_registry,
-                sc_transaction_pool::RevalidationType::Light,
-            );
-            Ok(pool)
-        })?
-        .with_import_queue_and_fprb(
-            |_config,
-             client,
-             backend,
-             fetcher,
-             _select_chain,
-             _tx_pool,
-             spawn_task_handle,
-             registry| {
-                let fetch_checker = fetcher
-                    .map(|fetcher| fetcher.checker().clone())
-                    .ok_or_else(|| {
-                        "Trying to start light import queue without active fetch checker"
-                    })?;
-                let grandpa_block_import = sc_finality_grandpa::light_block_import(
-                    client.clone(),
-                    backend,
-                    &(client.clone() as Arc<_>),
-                    Arc::new(fetch_checker),
-                )?;
-
-                let finality_proof_import = grandpa_block_import.clone();
-                let finality_proof_request_builder =
-                    finality_proof_import.create_finality_proof_request_builder();
-
-                let (babe_block_import, babe_link) = sc_consensus_babe::block_import(
-                    sc_consensus_babe::Config::get_or_compute(&*client)?,
-                    grandpa_block_import,
-                    client.clone(),
-                )?;
-
-                let import_queue = sc_consensus_babe::import_queue(
-                    babe_link,
-                    babe_block_import,
-                    None,
-                    Some(Box::new(finality_proof_import)),
-                    client,
-                    inherent_data_providers,
-                    spawn_task_handle,
-                    registry,
-                )?;
-
-                Ok((import_queue, finality_proof_request_builder))
-            },
-        )?
-        .with_finality_proof_provider(|client, backend| {
-            // GenesisAuthoritySetProvider is implemented for StorageAndProofProvider
-            let provider = client as Arc<dyn sc_finality_grandpa::StorageAndProofProvider<_, _>>;
-            Ok(Arc::new(sc_finality_grandpa::FinalityProofProvider::new(
-                backend, provider,
-            )) as _)
-        })?
-        .build()
-    }};
+///
+/// Does not go through [`new_partial`]: a light client builds on `on_demand`-backed light
+/// client/backend/transaction-pool types rather than [`FullClient`]/[`FullBackend`], so it
+/// can't share `new_partial`'s `PartialComponents` without templating that function over a
+/// second client kind.
+pub fn new_light<RuntimeApi, Executor>(config: Configuration) -> Result<TaskManager, ServiceError>
+where
+    Executor: sc_executor::NativeExecutionDispatch + 'static,
+    RuntimeApi: sp_api::ConstructRuntimeApi<node_primitives::Block, FullClient<RuntimeApi, Executor>>
+        + Send
+        + Sync
+        + 'static,
+    RuntimeApi::RuntimeApi: node_primitives::RuntimeApiCollection<StateBackend = sc_client_api::StateBackendFor<FullBackend, node_primitives::Block>>,
+{
+    let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+    let executor = executor::build_executor::<Executor>(&config);
+
+    let (client, backend, keystore_container, mut task_manager, on_demand) =
+        sc_service::new_light_parts::<node_primitives::Block, RuntimeApi, _>(&config, executor)?;
+
+    let select_chain = sc_consensus::LongestChain::new(backend.clone());
+
+    let transaction_pool = Arc::new(sc_transaction_pool::BasicPool::new_light(
+        config.transaction_pool.clone(),
+        config.prometheus_registry(),
+        task_manager.spawn_handle(),
+        client.clone(),
+        on_demand.clone(),
+    ));
+
+    let grandpa_block_import = sc_finality_grandpa::light_block_import(
+        client.clone(),
+        backend.clone(),
+        &(client.clone() as Arc<_>),
+        Arc::new(on_demand.checker().clone()),
+    )?;
+
+    let finality_proof_import = grandpa_block_import.clone();
+    let finality_proof_request_builder = finality_proof_import.create_finality_proof_request_builder();
+
+    let (babe_block_import, babe_link) = sc_consensus_babe::block_import(
+        sc_consensus_babe::Config::get_or_compute(&*client)?,
+        grandpa_block_import,
+        client.clone(),
+    )?;
+
+    let import_queue = sc_consensus_babe::import_queue(
+        babe_link,
+        babe_block_import,
+        None,
+        Some(Box::new(finality_proof_import)),
+        client.clone(),
+        inherent_data_providers,
+        &task_manager.spawn_handle(),
+        config.prometheus_registry(),
+    )?;
+
+    let (network, system_rpc_tx, network_starter) = sc_service::build_network(sc_service::BuildNetworkParams {
+        config: &config,
+        client: client.clone(),
+        transaction_pool: transaction_pool.clone(),
+        spawn_handle: task_manager.spawn_handle(),
+        import_queue,
+        block_announce_validator_builder: Some(Box::new(move |_| Box::new(finality_proof_request_builder))),
+        warp_sync: None,
+    })?;
+
+    sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+        network: network.clone(),
+        client: client.clone(),
+        keystore: keystore_container.sync_keystore(),
+        task_manager: &mut task_manager,
+        transaction_pool,
+        rpc_extensions_builder: Box::new(|_, _| Ok(())),
+        backend,
+        system_rpc_tx,
+        telemetry_connection_sinks: sc_service::TelemetryConnectionSinks::default(),
+        config,
+    })?;
+
+    network_starter.start_network();
+    Ok(task_manager)
 }
 
 /// IPCI chain services.
 pub mod ipci {
-    use sc_service::{config::Configuration, error::Result, AbstractService};
+    use sc_service::{Configuration, TaskManager};
 
     /// Create a new IPCI service for a full node.
-    pub fn new_full(config: Configuration) -> Result<impl AbstractService> {
-        new_full!(config, ipci_runtime::RuntimeApi, super::executor::Ipci)
+    pub fn new_full(config: Configuration) -> Result<TaskManager, sc_service::Error> {
+        super::new_full::<ipci_runtime::RuntimeApi, super::executor::Ipci>(config)
     }
 
     /// Create a new IPCI service for a light client.
-    pub fn new_light(config: Configuration) -> Result<impl AbstractService> {
-        new_light!(config, ipci_runtime::RuntimeApi, super::executor::Ipci)
+    pub fn new_light(config: Configuration) -> Result<TaskManager, sc_service::Error> {
+        super::new_light::<ipci_runtime::RuntimeApi, super::executor::Ipci>(config)
     }
 }
 
 ///  Robonomics chain services.
 pub mod robonomics {
-    use sc_service::{config::Configuration, error::Result, AbstractService};
+    use sc_service::{Configuration, TaskManager};
 
     /// Create a new Robonomics service for a full node.
-    pub fn new_full(config: Configuration) -> Result<impl AbstractService> {
-        new_full!(
-            config,
-            robonomics_runtime::RuntimeApi,
-            super::executor::Robonomics
-        )
+    pub fn new_full(config: Configuration) -> Result<TaskManager, sc_service::Error> {
+        super::new_full::<robonomics_runtime::RuntimeApi, super::executor::Robonomics>(config)
     }
 
     /// Create a new Robonomics service for a light client.
-    pub fn new_light(config: Configuration) -> Result<impl AbstractService> {
-        new_light!(
-            config,
-            robonomics_runtime::RuntimeApi,
-            super::executor::Robonomics
-        )
+    pub fn new_light(config: Configuration) -> Result<TaskManager, sc_service::Error> {
+        super::new_light::<robonomics_runtime::RuntimeApi, super::executor::Robonomics>(config)
     }
 }